@@ -0,0 +1,92 @@
+//! TOML configuration for plot output and series, in the spirit of a
+//! `Chart`/`Shot`-style config: an `Output` section controlling where and
+//! how figures are rendered, and a list of `Series` describing what gets
+//! plotted. CLI flags (`-o`, `--terminal`, `--width`, `--height`) still
+//! override individual fields when present.
+
+use std::fs;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Field {
+    Power,
+    Current,
+    Voltage,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Series {
+    pub field: Field,
+    pub caption: String,
+    pub color: String,
+    /// Samples whose value falls below this threshold are dropped before
+    /// plotting.
+    #[serde(default)]
+    pub cutoff: Option<f32>,
+    /// Plot this series against the right-hand Y2 axis instead of the
+    /// primary Y axis, for series whose scale (e.g. voltage) would
+    /// otherwise be dwarfed by others (e.g. power) sharing the same axis.
+    #[serde(default)]
+    pub secondary_axis: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Output {
+    #[serde(default = "Output::default_dir")]
+    pub dir: String,
+    #[serde(default = "Output::default_terminal")]
+    pub terminal: String,
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+}
+
+impl Output {
+    fn default_dir() -> String {
+        String::from("./")
+    }
+
+    fn default_terminal() -> String {
+        String::from("pdfcairo")
+    }
+}
+
+impl Default for Output {
+    fn default() -> Output {
+        Output {
+            dir: Output::default_dir(),
+            terminal: Output::default_terminal(),
+            width: None,
+            height: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Config {
+    #[serde(default)]
+    pub output: Output,
+    #[serde(default)]
+    pub series: Vec<Series>,
+}
+
+impl Config {
+    pub fn load(path: &str) -> Result<Config, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read config '{}': {}", path, e))?;
+        toml::from_str(&text).map_err(|e| format!("Could not parse config '{}': {}", path, e))
+    }
+
+    /// The single blue "Power" line plotted when no config is given,
+    /// matching the tool's original hard-coded behavior.
+    pub fn default_series() -> Vec<Series> {
+        vec![Series {
+            field: Field::Power,
+            caption: String::from("Power"),
+            color: String::from("blue"),
+            cutoff: None,
+            secondary_axis: false,
+        }]
+    }
+}
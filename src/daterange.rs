@@ -0,0 +1,127 @@
+//! Parses the `--from`/`--to` date-range flags, accepting plain
+//! `YYYY-MM-DD` dates as well as a small set of relative expressions
+//! (`today`, `yesterday`, `-7d`, `-2w`, `start-of-month`), resolved
+//! against the configured `--time-zone` offset.
+
+use std::path::Path;
+
+use chrono::prelude::*;
+use chrono::Duration;
+
+/// Resolve a single date expression to the midnight (in `tz`) that starts
+/// that day.
+fn parse_day_start(
+    expr: &str,
+    tz: FixedOffset,
+    now: DateTime<FixedOffset>,
+) -> Result<Date<FixedOffset>, String> {
+    let today = now.date();
+
+    if expr == "today" {
+        return Ok(today);
+    }
+    if expr == "yesterday" {
+        return Ok(today - Duration::days(1));
+    }
+    if expr == "start-of-month" {
+        return Ok(tz.ymd(today.year(), today.month(), 1));
+    }
+    if let Some(rest) = expr.strip_prefix('-') {
+        if let Some(n) = rest.strip_suffix('d') {
+            let days: i64 = n.parse().map_err(|_| format!("Invalid date expression: {}", expr))?;
+            return Ok(today - Duration::days(days));
+        }
+        if let Some(n) = rest.strip_suffix('w') {
+            let weeks: i64 = n.parse().map_err(|_| format!("Invalid date expression: {}", expr))?;
+            return Ok(today - Duration::weeks(weeks));
+        }
+        return Err(format!("Invalid date expression: {}", expr));
+    }
+
+    let naive = NaiveDate::parse_from_str(expr, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date expression: {}", expr))?;
+    Ok(tz.from_local_date(&naive).unwrap())
+}
+
+/// Resolve `--from`/`--to` into an inclusive-start, exclusive-end
+/// millisecond window. A missing bound resolves to the full range of
+/// representable timestamps.
+pub fn resolve_range(
+    from: Option<&str>,
+    to: Option<&str>,
+    tz: FixedOffset,
+    now: DateTime<FixedOffset>,
+) -> Result<(i64, i64), String> {
+    let start_ms = match from {
+        Some(expr) => parse_day_start(expr, tz, now)?.and_hms(0, 0, 0).timestamp_millis(),
+        None => i64::min_value(),
+    };
+    let end_ms = match to {
+        Some(expr) => {
+            let day = parse_day_start(expr, tz, now)?;
+            (day.and_hms(0, 0, 0) + Duration::days(1)).timestamp_millis()
+        }
+        None => i64::max_value(),
+    };
+    Ok((start_ms, end_ms))
+}
+
+/// Whether the log file at `path`, whose date is embedded in its file
+/// name as `YYYY-MM-DD.log.xz`, could contain any sample inside
+/// `[start_ms, end_ms)`. Files whose name doesn't parse as a date are
+/// always kept, since we can't rule them out without decompressing them.
+pub fn file_in_range(path: &str, start_ms: i64, end_ms: i64, tz: FixedOffset) -> bool {
+    let stem = match Path::new(path).file_name().and_then(|n| n.to_str()) {
+        Some(name) => name.trim_end_matches(".log.xz"),
+        None => return true,
+    };
+
+    let naive = match NaiveDate::parse_from_str(stem, "%Y-%m-%d") {
+        Ok(date) => date,
+        Err(_) => return true,
+    };
+
+    let day_start = tz.from_local_date(&naive).unwrap().and_hms(0, 0, 0).timestamp_millis();
+    let day_end = day_start + 24 * 60 * 60 * 1000;
+    day_end > start_ms && day_start < end_ms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc_now(year: i32, month: u32, day: u32) -> (FixedOffset, DateTime<FixedOffset>) {
+        let tz = FixedOffset::east(0);
+        let now = tz.ymd(year, month, day).and_hms(12, 0, 0);
+        (tz, now)
+    }
+
+    #[test]
+    fn parses_relative_days_and_weeks() {
+        let (tz, now) = utc_now(2026, 3, 15);
+        assert_eq!(parse_day_start("-7d", tz, now).unwrap(), tz.ymd(2026, 3, 8));
+        assert_eq!(parse_day_start("-2w", tz, now).unwrap(), tz.ymd(2026, 3, 1));
+    }
+
+    #[test]
+    fn parses_today_yesterday_and_start_of_month() {
+        let (tz, now) = utc_now(2026, 3, 15);
+        assert_eq!(parse_day_start("today", tz, now).unwrap(), tz.ymd(2026, 3, 15));
+        assert_eq!(parse_day_start("yesterday", tz, now).unwrap(), tz.ymd(2026, 3, 14));
+        assert_eq!(parse_day_start("start-of-month", tz, now).unwrap(), tz.ymd(2026, 3, 1));
+    }
+
+    #[test]
+    fn parses_explicit_dates_and_rejects_garbage() {
+        let (tz, now) = utc_now(2026, 3, 15);
+        assert_eq!(parse_day_start("2026-01-02", tz, now).unwrap(), tz.ymd(2026, 1, 2));
+        assert!(parse_day_start("not-a-date", tz, now).is_err());
+    }
+
+    #[test]
+    fn resolve_range_end_is_exclusive_day_after() {
+        let (tz, now) = utc_now(2026, 3, 15);
+        let (start_ms, end_ms) = resolve_range(Some("2026-03-10"), Some("2026-03-10"), tz, now).unwrap();
+        assert_eq!(end_ms - start_ms, 24 * 60 * 60 * 1000);
+    }
+}
@@ -0,0 +1,116 @@
+//! Sort-and-dedup pass for overlapping log files: collapses exact
+//! timestamp duplicates (a device re-sending the same sample) and,
+//! optionally, coalesces samples that land within a `--merge-window` of
+//! each other by averaging their current/voltage, the same way a logger
+//! that double-logs at slightly different timestamps would want folded
+//! into one reading.
+
+use crate::Record;
+
+/// Removes exact-duplicate timestamps and, if `merge_window_ms > 0`, folds
+/// runs of samples within `merge_window_ms` of each other into one
+/// averaged `Record`. `recs` must already be sorted. Returns the number of
+/// records dropped.
+pub fn dedup_merge(recs: &mut Vec<Record>, merge_window_ms: i64) -> usize {
+    let before = recs.len();
+
+    // `Record`'s `Eq`/`Ord` key only on `timestamp_ms`, so this already
+    // collapses exact-duplicate timestamps.
+    recs.dedup();
+
+    if merge_window_ms > 0 {
+        merge_close(recs, merge_window_ms);
+    }
+
+    before - recs.len()
+}
+
+/// Forward scan that folds a run of samples, each within `merge_window_ms`
+/// of the *previous* sample (not the run's start), into a single averaged
+/// `Record` anchored at the mean of the run's timestamps. Because each
+/// sample is measured against the previous one rather than the run start,
+/// only the gap between neighbours is bounded by `merge_window_ms`: an
+/// evenly-spaced run whose steps all fit the window collapses into one
+/// point however long it spans.
+fn merge_close(recs: &mut Vec<Record>, merge_window_ms: i64) {
+    let mut merged: Vec<Record> = Vec::with_capacity(recs.len());
+    let mut run_iter = recs.drain(..);
+
+    let mut run_prev = match run_iter.next() {
+        Some(rec) => rec,
+        None => return,
+    };
+    let mut timestamp_acc = run_prev.timestamp_ms;
+    let mut current_acc = run_prev.current;
+    let mut voltage_acc = run_prev.voltage;
+    let mut count: i64 = 1;
+
+    for rec in run_iter {
+        if rec.timestamp_ms - run_prev.timestamp_ms <= merge_window_ms {
+            timestamp_acc += rec.timestamp_ms;
+            current_acc += rec.current;
+            voltage_acc += rec.voltage;
+            count += 1;
+        } else {
+            merged.push(Record {
+                timestamp_ms: timestamp_acc / count,
+                current: current_acc / count as f32,
+                voltage: voltage_acc / count as f32,
+            });
+            timestamp_acc = rec.timestamp_ms;
+            current_acc = rec.current;
+            voltage_acc = rec.voltage;
+            count = 1;
+        }
+        run_prev = rec;
+    }
+
+    merged.push(Record {
+        timestamp_ms: timestamp_acc / count,
+        current: current_acc / count as f32,
+        voltage: voltage_acc / count as f32,
+    });
+
+    *recs = merged;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rec(timestamp_ms: i64, current: f32, voltage: f32) -> Record {
+        Record { timestamp_ms, current, voltage }
+    }
+
+    #[test]
+    fn dedup_merge_drops_exact_duplicate_timestamps() {
+        let mut recs = vec![rec(0, 1.0, 10.0), rec(0, 2.0, 10.0), rec(1000, 3.0, 10.0)];
+        let dropped = dedup_merge(&mut recs, 0);
+        assert_eq!(dropped, 1);
+        assert_eq!(recs.len(), 2);
+    }
+
+    #[test]
+    fn merge_close_anchors_run_at_the_midpoint_timestamp() {
+        let mut recs = vec![rec(0, 0.0, 10.0), rec(100, 10.0, 10.0)];
+        merge_close(&mut recs, 100);
+        assert_eq!(recs.len(), 1);
+        assert_eq!(recs[0].timestamp_ms, 50);
+        assert_eq!(recs[0].current, 5.0);
+    }
+
+    #[test]
+    fn merge_close_bounds_runs_by_the_previous_sample() {
+        // Each step is exactly at the merge window, so an evenly-spaced
+        // run keeps growing rather than being cut off after one merge -
+        // unlike measuring against the run's start, which would split it.
+        let mut recs = vec![rec(0, 1.0, 10.0), rec(100, 1.0, 10.0), rec(200, 1.0, 10.0)];
+        merge_close(&mut recs, 100);
+        assert_eq!(recs.len(), 1);
+
+        // But a gap wider than the window starts a new run.
+        let mut recs = vec![rec(0, 1.0, 10.0), rec(100, 1.0, 10.0), rec(300, 1.0, 10.0)];
+        merge_close(&mut recs, 100);
+        assert_eq!(recs.len(), 2);
+    }
+}
@@ -0,0 +1,147 @@
+//! Integrates the consolidated power series into Wh/kWh energy totals,
+//! rolled up per day, ISO week (anchored on Monday, like the week-bucketing
+//! used by timetracker-style tools) and calendar month.
+
+use chrono::prelude::*;
+
+use crate::rrd::Cdp;
+
+pub struct DayTotal {
+    pub date: NaiveDate,
+    pub wh: f64,
+}
+
+pub struct WeekTotal {
+    pub iso_year: i32,
+    pub iso_week: u32,
+    pub wh: f64,
+}
+
+pub struct MonthTotal {
+    pub year: i32,
+    pub month: u32,
+    pub wh: f64,
+}
+
+pub struct EnergyReport {
+    pub days: Vec<DayTotal>,
+    pub weeks: Vec<WeekTotal>,
+    pub months: Vec<MonthTotal>,
+    pub total_wh: f64,
+}
+
+/// Trapezoidal energy for one consecutive pair of CDPs, in watt-hours, or
+/// `None` if the pair is more than `gap_limit_ms` apart and should be
+/// skipped rather than interpolated across (the logger likely missed data
+/// rather than power staying constant).
+fn pair_wh(a: &Cdp, b: &Cdp, gap_limit_ms: i64) -> Option<f64> {
+    let dt_ms = b.timestamp_ms - a.timestamp_ms;
+    if dt_ms <= 0 || dt_ms > gap_limit_ms {
+        return None;
+    }
+    Some(0.5 * (a.power as f64 + b.power as f64) * (dt_ms as f64) / 3_600_000.0)
+}
+
+/// Trapezoidal integration of power over time, in watt-hours.
+pub fn trapezoid_wh(cdps: &[Cdp], gap_limit_ms: i64) -> f64 {
+    cdps.windows(2)
+        .filter_map(|pair| pair_wh(&pair[0], &pair[1], gap_limit_ms))
+        .sum()
+}
+
+/// Integrate `cdps` into per-day, per-ISO-week and per-month totals, using
+/// the same `tz` day-boundary logic as the plotting loop.
+pub fn integrate(cdps: &[Cdp], gap_limit_ms: i64, tz: FixedOffset) -> EnergyReport {
+    let mut days: Vec<DayTotal> = Vec::new();
+
+    for pair in cdps.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        let wh = match pair_wh(a, b, gap_limit_ms) {
+            Some(wh) => wh,
+            None => continue,
+        };
+        let date = tz
+            .from_utc_datetime(&NaiveDateTime::from_timestamp(a.timestamp_ms / 1000, 0))
+            .date()
+            .naive_local();
+
+        match days.last_mut() {
+            Some(d) if d.date == date => d.wh += wh,
+            _ => days.push(DayTotal { date: date, wh: wh }),
+        }
+    }
+
+    let mut weeks: Vec<WeekTotal> = Vec::new();
+    let mut months: Vec<MonthTotal> = Vec::new();
+    for day in &days {
+        let iso = day.date.iso_week();
+        match weeks.last_mut() {
+            Some(w) if w.iso_year == iso.year() && w.iso_week == iso.week() => w.wh += day.wh,
+            _ => weeks.push(WeekTotal { iso_year: iso.year(), iso_week: iso.week(), wh: day.wh }),
+        }
+        match months.last_mut() {
+            Some(m) if m.year == day.date.year() && m.month == day.date.month() => m.wh += day.wh,
+            _ => months.push(MonthTotal { year: day.date.year(), month: day.date.month(), wh: day.wh }),
+        }
+    }
+
+    let total_wh = trapezoid_wh(cdps, gap_limit_ms);
+
+    EnergyReport { days: days, weeks: weeks, months: months, total_wh: total_wh }
+}
+
+pub fn print_report(report: &EnergyReport) {
+    println!("\nEnergy summary:");
+    println!("{:<12} {:>10}", "Day", "Wh");
+    for day in &report.days {
+        println!("{:<12} {:>10.1}", day.date.format("%Y-%m-%d"), day.wh);
+    }
+
+    println!("\n{:<10} {:>10}", "ISO week", "kWh");
+    for week in &report.weeks {
+        let label = format!("{}-W{:02}", week.iso_year, week.iso_week);
+        println!("{:<10} {:>10.2}", label, week.wh / 1000.0);
+    }
+
+    println!("\n{:<10} {:>10}", "Month", "kWh");
+    for month in &report.months {
+        let label = format!("{}-{:02}", month.year, month.month);
+        println!("{:<10} {:>10.2}", label, month.wh / 1000.0);
+    }
+
+    println!("\nTotal: {:.2} kWh", report.total_wh / 1000.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cdp(timestamp_ms: i64, power: f32) -> Cdp {
+        Cdp { timestamp_ms, current: 0.0, voltage: 0.0, power }
+    }
+
+    #[test]
+    fn trapezoid_wh_integrates_constant_power_over_an_hour() {
+        let cdps = [cdp(0, 100.0), cdp(3_600_000, 100.0)];
+        assert_eq!(trapezoid_wh(&cdps, 3_600_000), 100.0);
+    }
+
+    #[test]
+    fn trapezoid_wh_skips_pairs_beyond_the_gap_limit() {
+        let cdps = [cdp(0, 100.0), cdp(3_600_000, 100.0), cdp(7_200_001, 200.0)];
+        // The first pair spans exactly the gap limit and should integrate
+        // normally; a logger dropout wider than the limit must not be
+        // bridged with an interpolated 200.0 W reading.
+        assert_eq!(trapezoid_wh(&cdps, 3_600_000), 100.0);
+    }
+
+    #[test]
+    fn pair_wh_rejects_non_positive_or_too_wide_gaps() {
+        let a = cdp(1000, 100.0);
+        let b = cdp(1000, 100.0);
+        assert_eq!(pair_wh(&a, &b, 10_000), None);
+
+        let c = cdp(2000, 100.0);
+        assert_eq!(pair_wh(&a, &c, 500), None);
+    }
+}
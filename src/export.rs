@@ -0,0 +1,98 @@
+//! Writes the consolidated series out as CSV or newline-delimited JSON, so
+//! the parsed data can feed other tools instead of being locked inside
+//! gnuplot.
+
+use std::io::{self, Write};
+
+use crate::rrd::Cdp;
+
+/// Write a line, treating a broken pipe (e.g. the output end of `| head`)
+/// as a normal reason to stop rather than a bug, and still panicking on any
+/// other I/O error.
+macro_rules! writeln_or_stop {
+    ($out:expr, $($arg:tt)*) => {
+        if let Err(e) = writeln!($out, $($arg)*) {
+            if e.kind() == io::ErrorKind::BrokenPipe {
+                return;
+            }
+            panic!("{}", e);
+        }
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Result<Format, String> {
+        match name {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            other => Err(format!("Unknown export format '{}'", other)),
+        }
+    }
+}
+
+/// Writes `timestamp_ms,current,voltage,power` rows to stdout in the
+/// requested format.
+pub fn write_cdps(cdps: &[Cdp], format: Format) {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    write_cdps_to(&mut out, cdps, format);
+}
+
+/// Does the actual formatting, against any `Write`, so the CSV/JSON layout
+/// can be unit-tested without going through stdout.
+fn write_cdps_to<W: Write>(out: &mut W, cdps: &[Cdp], format: Format) {
+    match format {
+        Format::Csv => {
+            writeln_or_stop!(out, "timestamp_ms,current,voltage,power");
+            for cdp in cdps {
+                writeln_or_stop!(out, "{},{},{},{}", cdp.timestamp_ms, cdp.current, cdp.voltage, cdp.power);
+            }
+        }
+        Format::Json => {
+            for cdp in cdps {
+                writeln_or_stop!(
+                    out,
+                    "{{\"timestamp_ms\":{},\"current\":{},\"voltage\":{},\"power\":{}}}",
+                    cdp.timestamp_ms, cdp.current, cdp.voltage, cdp.power
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cdp(timestamp_ms: i64, current: f32, voltage: f32, power: f32) -> Cdp {
+        Cdp { timestamp_ms, current, voltage, power }
+    }
+
+    #[test]
+    fn csv_writes_a_header_and_one_row_per_cdp() {
+        let cdps = [cdp(0, 1.0, 10.0, 10.0), cdp(1000, 2.0, 11.0, 22.0)];
+        let mut out = Vec::new();
+        write_cdps_to(&mut out, &cdps, Format::Csv);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "timestamp_ms,current,voltage,power\n0,1,10,10\n1000,2,11,22\n"
+        );
+    }
+
+    #[test]
+    fn json_writes_one_object_per_line_with_no_header() {
+        let cdps = [cdp(0, 1.0, 10.0, 10.0)];
+        let mut out = Vec::new();
+        write_cdps_to(&mut out, &cdps, Format::Json);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"timestamp_ms\":0,\"current\":1,\"voltage\":10,\"power\":10}\n"
+        );
+    }
+}
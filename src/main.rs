@@ -2,48 +2,82 @@ extern crate lzma;
 extern crate gnuplot;
 extern crate docopt;
 extern crate chrono;
+extern crate toml;
 #[macro_use]
 extern crate serde_derive;
 use docopt::Docopt;
 use gnuplot::{Figure, Caption, Color};
 use std::fs;
 use std::env;
-use std::io::BufRead;
+use std::io::{BufRead, Read};
 use std::sync::{Mutex, Arc};
 use std::cmp::Ordering;
 use std::thread;
 use gnuplot::*;
 use chrono::prelude::*;
 
+mod rrd;
+mod energy;
+mod daterange;
+mod config;
+mod dedup;
+mod export;
+mod stats;
+use rrd::{ArchiveSpec, ConsolFn};
+use config::{Config, Field};
+
 const USAGE: &'static str = "
 Solar Power Ploter
 
 Usage:
-  __PROGNAME__ <logdir> [-o OUTDIR] [--avg=<sec>] [-t THREADS] [--time-zone=<tz>]
+  __PROGNAME__ plot <logdir> [options]
+  __PROGNAME__ export <logdir> [options] [--format=<fmt>]
+  __PROGNAME__ stats <logdir> [options]
   __PROGNAME__ (-h | --help)
 
 Options:
-  -h --help         Show this screen.
-  -o OUTDIR         Plot file name [default: ./]
-  --avg=<sec>       Take the average of 'sec' seconds [default: 300]
-  -t THREADS        Number of threads for processing the input data [default: 1]
-  --time-zone=<tz>  Specify the timezone [default: 0];
+  -h --help           Show this screen.
+  -o OUTDIR           Plot output directory, overrides the config file.
+  --avg=<sec>         Take the average of 'sec' seconds [default: 300]
+  -t THREADS          Number of threads for processing the input data [default: 1]
+  --time-zone=<tz>    Specify the timezone [default: 0];
+  --archive=<name>    Archive to use: custom, 5m-avg, hour-avg, hour-min, hour-max, day-avg, 5m-last [default: custom]
+  --from=<date>       Only include samples on/after this date (YYYY-MM-DD, today, yesterday, -7d, -2w, start-of-month)
+  --to=<date>         Only include samples on/before this date (same formats as --from)
+  --config=<path>     TOML config file describing output and series to plot.
+  --terminal=<term>   Gnuplot terminal (pngcairo, svg, pdfcairo), overrides the config file.
+  --width=<px>        Figure width in pixels, overrides the config file.
+  --height=<px>       Figure height in pixels, overrides the config file.
+  --merge-window=<ms>  Coalesce samples within this many milliseconds of each other [default: 0]
+  --format=<fmt>      Export format: csv or json [default: csv]
 ";
 
 #[derive(Debug, Deserialize)]
 struct Args {
+    cmd_plot: bool,
+    cmd_export: bool,
+    cmd_stats: bool,
     arg_logdir: String,
-    flag_o: String,
+    flag_o: Option<String>,
     flag_avg: i32,
     flag_t: i32,
     flag_time_zone: i32,
+    flag_archive: String,
+    flag_from: Option<String>,
+    flag_to: Option<String>,
+    flag_config: Option<String>,
+    flag_terminal: Option<String>,
+    flag_width: Option<u32>,
+    flag_height: Option<u32>,
+    flag_merge_window: i64,
+    flag_format: String,
 }
 
 #[derive (Clone)]
-struct Record {
-    timestamp_ms: i64,
-    current: f32,
-    voltage: f32,
+pub(crate) struct Record {
+    pub(crate) timestamp_ms: i64,
+    pub(crate) current: f32,
+    pub(crate) voltage: f32,
 }
 
 impl Ord for Record {
@@ -125,7 +159,7 @@ impl Dispatcher {
         }
     }
 
-    fn set_log_dir(&mut self, path: &str) {
+    fn set_log_dir(&mut self, path: &str, start_ms: i64, end_ms: i64, tz: FixedOffset) {
         let log_path = fs::read_dir(path).expect("Directory not accessible.");
         let logs = log_path.map(|entry| {
 		    let entry = entry.unwrap();
@@ -136,7 +170,9 @@ impl Dispatcher {
 	    }).collect::<Vec<String>>();
         for file in logs {
             if file.ends_with(".log.xz") {
-                self.files.push(file);
+                if daterange::file_in_range(&file, start_ms, end_ms, tz) {
+                    self.files.push(file);
+                }
             }
         }
     }
@@ -157,7 +193,11 @@ impl Dispatcher {
 }
 
 fn parse_file(file: &str, recs: &mut Vec<Record>) {
-    let decompressed = lzma::decompress(&fs::read(file).unwrap()).expect("Corrupt xz file!");
+    let mut decompressed = Vec::new();
+    lzma::open(file)
+        .expect("Corrupt xz file!")
+        .read_to_end(&mut decompressed)
+        .expect("Corrupt xz file!");
     let file_reader = std::io::BufReader::new(decompressed.as_slice());
 
     for (index, line) in file_reader.lines().enumerate() {
@@ -168,42 +208,67 @@ fn parse_file(file: &str, recs: &mut Vec<Record>) {
     }
 }
 
-fn take_avg(recs: &Vec<Record>, start_index: usize, delta_time_ms: i64) -> (usize, Record) {
-    let start_time = recs[start_index].timestamp_ms;
-    let mut rec_index = start_index;
-    let mut avg_index: i64 = 0;
-    let mut current_acc: f32 = 0.0;
-    let mut voltage_acc: f32 = 0.0;
+/// Build the fixed set of RRD-style archives consolidated from `recs` in a
+/// single pass: the user-selected `--avg` window (`custom`), plus a small
+/// bank of canned resolutions (5-minute average/last-sample, hourly
+/// min/avg/max, daily average) so callers can switch zoom levels without
+/// re-reading the log files. `rows` is sized to the record count so none of
+/// the archives ever have to evict data for this one-shot report.
+fn build_archives(recs: &[Record], avg_sec: i64, tz_offset_ms: i64) -> Vec<rrd::Archive> {
+    let rows = recs.len().max(1);
+    let specs = [
+        ArchiveSpec { cf: ConsolFn::Average, step_ms: avg_sec * 1000, rows: rows },
+        ArchiveSpec { cf: ConsolFn::Average, step_ms: 5 * 60 * 1000, rows: rows },
+        ArchiveSpec { cf: ConsolFn::Average, step_ms: 60 * 60 * 1000, rows: rows },
+        ArchiveSpec { cf: ConsolFn::Min, step_ms: 60 * 60 * 1000, rows: rows },
+        ArchiveSpec { cf: ConsolFn::Max, step_ms: 60 * 60 * 1000, rows: rows },
+        ArchiveSpec { cf: ConsolFn::Average, step_ms: 24 * 60 * 60 * 1000, rows: rows },
+        ArchiveSpec { cf: ConsolFn::Last, step_ms: 5 * 60 * 1000, rows: rows },
+    ];
+    rrd::build_archives(recs, &specs, tz_offset_ms)
+}
 
-    for rec in &recs[start_index..] {
-        if (rec.timestamp_ms - start_time) >= delta_time_ms {
-            break;
+/// Map an `--archive` flag value to the index of the matching archive
+/// produced by `build_archives`.
+fn archive_index(name: &str) -> usize {
+    match name {
+        "custom" => 0,
+        "5m-avg" => 1,
+        "hour-avg" => 2,
+        "hour-min" => 3,
+        "hour-max" => 4,
+        "day-avg" => 5,
+        "5m-last" => 6,
+        other => {
+            eprintln!("Error: unknown archive '{}'.", other);
+            std::process::exit(2);
         }
-        current_acc += rec.current;
-        voltage_acc += rec.voltage;
-        avg_index += 1;
-        rec_index += 1;
     }
-
-    (rec_index, Record {
-        timestamp_ms: start_time,
-        current: current_acc / if avg_index > 0 {(avg_index as f32)} else {1.0},
-        voltage: voltage_acc / if avg_index > 0 {(avg_index as f32)} else {1.0},
-    })
 }
 
-fn main() {
-    /*
-     * Parse arguments
-     */
-    let usage = USAGE.replace("__PROGNAME__", &env::args().nth(0).unwrap());
-    let args: Args = Docopt::new(usage).and_then(|d| d.deserialize()).unwrap_or_else(|e| e.exit());
+/// Shared core: decompress and parse every log file in range, then sort,
+/// date-filter, dedup/merge and consolidate into the archive selected by
+/// `--archive`. This is the one pipeline `plot`, `export` and `stats` all
+/// run before going their separate ways. Returns the consolidated series
+/// together with that archive's `step_ms`, so callers can derive things
+/// like the energy gap threshold from the series' actual spacing.
+fn build_pipeline(args: &Args, time_zone: FixedOffset) -> (Vec<rrd::Cdp>, i64) {
+    let now = time_zone.from_utc_datetime(&Utc::now().naive_utc());
+    let (range_start_ms, range_end_ms) = daterange::resolve_range(
+        args.flag_from.as_deref(),
+        args.flag_to.as_deref(),
+        time_zone,
+        now,
+    ).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(2);
+    });
 
     let disp_mut = Arc::new(Mutex::new(Dispatcher::new()));
 
     {
         let mut disp = disp_mut.lock().unwrap();
-        disp.set_log_dir(&args.arg_logdir);
+        disp.set_log_dir(&args.arg_logdir, range_start_ms, range_end_ms, time_zone);
         if disp.files.len() == 0 {
             eprintln!("Error: directory '{}' doesn't contain log files.", &args.arg_logdir);
             std::process::exit(2);
@@ -246,26 +311,83 @@ fn main() {
         t.join().unwrap();
     }
 
-    let mut avg_recs: Vec<Record> = Vec::new();
+    let avg_recs: Vec<rrd::Cdp>;
+    let step_ms: i64;
     {
         let mut disp = disp_mut.lock().unwrap();
         println!("Records: {}", disp.recs.len());
         println!("Sorting...");
         disp.recs.sort();
-        println!("Averaging [{} seconds]...", args.flag_avg);
-        let mut next_index: usize = 0;
-        loop {
-            let res = take_avg(&disp.recs, next_index, (args.flag_avg as i64) * 1000);
-            next_index = res.0;
-            avg_recs.push(res.1);
-            if (next_index + 20) >= disp.recs.len() {
-                break;
-            }
-        }
+        disp.recs.retain(|r| r.timestamp_ms >= range_start_ms && r.timestamp_ms < range_end_ms);
+        let dropped = dedup::dedup_merge(&mut disp.recs, args.flag_merge_window);
+        println!("Dropped {} duplicate/merged records.", dropped);
+        println!("Consolidating [{} seconds, archive '{}']...", args.flag_avg, args.flag_archive);
+        let tz_offset_ms = (args.flag_time_zone as i64) * 3600 * 1000;
+        let archives = build_archives(&disp.recs, args.flag_avg as i64, tz_offset_ms);
+        let archive = &archives[archive_index(&args.flag_archive)];
+        step_ms = archive.step_ms;
+        avg_recs = archive.iter().cloned().collect();
         println!("Records avg count: {}", avg_recs.len());
         disp.recs.clear();
     }
 
+    (avg_recs, step_ms)
+}
+
+fn main() {
+    /*
+     * Parse arguments
+     */
+    let usage = USAGE.replace("__PROGNAME__", &env::args().nth(0).unwrap());
+    let args: Args = Docopt::new(usage).and_then(|d| d.deserialize()).unwrap_or_else(|e| e.exit());
+
+    let time_zone = FixedOffset::east(args.flag_time_zone * 3600);
+    let (avg_recs, step_ms) = build_pipeline(&args, time_zone);
+
+    if args.cmd_export {
+        let format = export::Format::parse(&args.flag_format).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        });
+        export::write_cdps(&avg_recs, format);
+        return;
+    }
+
+    if args.cmd_stats {
+        stats::print_per_day(&avg_recs, time_zone);
+        return;
+    }
+
+    let mut config = match &args.flag_config {
+        Some(path) => Config::load(path).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        }),
+        None => Config { output: Default::default(), series: Config::default_series() },
+    };
+    if config.series.is_empty() {
+        config.series = Config::default_series();
+    }
+    if let Some(dir) = &args.flag_o {
+        config.output.dir = dir.clone();
+    }
+    if let Some(terminal) = &args.flag_terminal {
+        config.output.terminal = terminal.clone();
+    }
+    if args.flag_width.is_some() {
+        config.output.width = args.flag_width;
+    }
+    if args.flag_height.is_some() {
+        config.output.height = args.flag_height;
+    }
+
+    // Consecutive CDPs in the selected archive are normally `step_ms` apart;
+    // anything wider than a couple of steps means the logger actually missed
+    // data, so skip it instead of interpolating across the gap.
+    let gap_limit_ms = 2 * step_ms;
+    let energy_report = energy::integrate(&avg_recs, gap_limit_ms, time_zone);
+    energy::print_report(&energy_report);
+
     println!("Ploting...");
     let mut rec_index: usize = 0;
 
@@ -276,24 +398,20 @@ fn main() {
         if rec_index >= avg_recs.len() {
             break;
         }
-        let mut x_time: Vec<f32> = Vec::new();
-        let mut y_power: Vec<f32> = Vec::new();
-        let time_zone = FixedOffset::east(args.flag_time_zone * 3600);
         let start_date = NaiveDateTime::from_timestamp(avg_recs[rec_index].timestamp_ms / 1000, 0);
         let start_date: DateTime<FixedOffset> = time_zone.from_utc_datetime(&start_date);
         let start_day = start_date.day();
 
         let mut count: usize = 0;
-        for rec in &avg_recs[rec_index..] {
-            let actual_date = NaiveDateTime::from_timestamp(rec.timestamp_ms / 1000, 0);
+        for cdp in &avg_recs[rec_index..] {
+            let actual_date = NaiveDateTime::from_timestamp(cdp.timestamp_ms / 1000, 0);
             let actual_date: DateTime<FixedOffset> = time_zone.from_utc_datetime(&actual_date);
             if start_day != actual_date.day() {
                 break;
             }
-            x_time.push(actual_date.hour() as f32 + actual_date.minute() as f32 / 60.0);
-            y_power.push(rec.current * rec.voltage);
             count += 1;
         }
+        let day_cdps = &avg_recs[rec_index..rec_index + count];
         rec_index += count;
 
         let date_str = start_date.format("%Y-%m-%d").to_string();
@@ -303,22 +421,74 @@ fn main() {
         title.push_str("Power Plot - ");
         title.push_str(&date_str);
         title.push_str(&time_zone_str);
-        fg.axes2d()
-            .set_title(&title, &[])
-            .lines(&x_time, &y_power, &[Caption("Power"), Color("blue")])
+        if let Some(day) = energy_report.days.iter().find(|d| d.date == start_date.date().naive_local()) {
+            title.push_str(&format!(" ({:.2} kWh)", day.wh / 1000.0));
+        }
+
+        let axes = fg.axes2d();
+        axes.set_title(&title, &[])
             .set_x_label("Day hour", &[])
             .set_y_label("Power [W]", &[])
             .set_grid_options(true, &[LineStyle(DotDotDash), Color("gray")])
 		    .set_x_grid(true)
 		    .set_y_grid(true);
 
+        if config.series.iter().any(|s| s.secondary_axis) {
+            axes.set_y2_label("Secondary axis", &[])
+                .set_y2_ticks(Some((Auto, 0)), &[], &[]);
+        }
+
+        for series in &config.series {
+            let mut x_series: Vec<f32> = Vec::new();
+            let mut y_series: Vec<f32> = Vec::new();
+            for cdp in day_cdps {
+                let value = series_value(cdp, series.field);
+                if let Some(cutoff) = series.cutoff {
+                    if value < cutoff {
+                        continue;
+                    }
+                }
+                let actual_date = NaiveDateTime::from_timestamp(cdp.timestamp_ms / 1000, 0);
+                let actual_date: DateTime<FixedOffset> = time_zone.from_utc_datetime(&actual_date);
+                x_series.push(actual_date.hour() as f32 + actual_date.minute() as f32 / 60.0);
+                y_series.push(value);
+            }
+            let mut options = vec![Caption(series.caption.as_str()), Color(series.color.as_str())];
+            if series.secondary_axis {
+                options.push(Axes(XAxis::X1, YAxis::Y2));
+            }
+            axes.lines(&x_series, &y_series, &options);
+        }
+
         let mut filename = String::new();
-        filename.push_str(&args.flag_o);
+        filename.push_str(&config.output.dir);
         filename.push_str("/");
         filename.push_str(&date_str);
-        filename.push_str(".pdf");
-        fg.set_terminal("pdfcairo", &filename);
+        filename.push_str(".");
+        filename.push_str(terminal_extension(&config.output.terminal));
+        let terminal = match (config.output.width, config.output.height) {
+            (Some(width), Some(height)) => format!("{} size {},{}", config.output.terminal, width, height),
+            _ => config.output.terminal.clone(),
+        };
+        fg.set_terminal(&terminal, &filename);
         fg.show();
     }
     println!("Done!");
 }
+
+fn series_value(cdp: &rrd::Cdp, field: Field) -> f32 {
+    match field {
+        Field::Power => cdp.power,
+        Field::Current => cdp.current,
+        Field::Voltage => cdp.voltage,
+    }
+}
+
+fn terminal_extension(terminal: &str) -> &str {
+    match terminal {
+        "pngcairo" | "png" => "png",
+        "svg" => "svg",
+        "pdfcairo" | "pdf" => "pdf",
+        other => other,
+    }
+}
@@ -0,0 +1,248 @@
+//! Round-robin-database-style multi-resolution consolidation, modeled after
+//! the archive scheme used by `proxmox-rrd`: a sorted stream of samples is
+//! walked once and folded into several fixed-size archives at different
+//! step sizes, so a single pass over the raw records can serve plots at
+//! several zoom levels without re-reading the `.log.xz` files.
+
+use crate::Record;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConsolFn {
+    Average,
+    Min,
+    Max,
+    Last,
+}
+
+/// One consolidated data point: the result of folding every sample that
+/// fell inside a single `step_ms` bucket down to one point per archive.
+///
+/// `power` is derived from `current * voltage` on the raw samples before
+/// consolidation (rather than recomputed from the consolidated
+/// current/voltage), so that `Min`/`Max` archives reflect true power
+/// extremes instead of the extremes of current and voltage taken
+/// independently.
+#[derive(Debug, Clone, Copy)]
+pub struct Cdp {
+    pub timestamp_ms: i64,
+    pub current: f32,
+    pub voltage: f32,
+    pub power: f32,
+}
+
+pub struct ArchiveSpec {
+    pub cf: ConsolFn,
+    pub step_ms: i64,
+    pub rows: usize,
+}
+
+/// A single fixed-resolution archive: a ring buffer of `Cdp`s produced by
+/// consolidating samples with `cf` over `step_ms` buckets. Once `rows` CDPs
+/// have been produced, the oldest one is overwritten, exactly like an RRD
+/// archive.
+pub struct Archive {
+    pub cf: ConsolFn,
+    pub step_ms: i64,
+    pub rows: usize,
+    ring: Vec<Cdp>,
+    head: usize,
+    bucket_start: i64,
+    sum_current: f32,
+    sum_voltage: f32,
+    sum_power: f32,
+    min_power: f32,
+    max_power: f32,
+    min_current: f32,
+    max_current: f32,
+    min_voltage: f32,
+    max_voltage: f32,
+    last_current: f32,
+    last_voltage: f32,
+    last_power: f32,
+    count: usize,
+}
+
+impl Archive {
+    fn new(spec: &ArchiveSpec) -> Archive {
+        Archive {
+            cf: spec.cf,
+            step_ms: spec.step_ms,
+            rows: spec.rows,
+            ring: Vec::with_capacity(spec.rows),
+            head: 0,
+            bucket_start: 0,
+            sum_current: 0.0,
+            sum_voltage: 0.0,
+            sum_power: 0.0,
+            min_power: f32::MAX,
+            max_power: f32::MIN,
+            min_current: f32::MAX,
+            max_current: f32::MIN,
+            min_voltage: f32::MAX,
+            max_voltage: f32::MIN,
+            last_current: 0.0,
+            last_voltage: 0.0,
+            last_power: 0.0,
+            count: 0,
+        }
+    }
+
+    fn reset_bucket(&mut self, start: i64) {
+        self.bucket_start = start;
+        self.sum_current = 0.0;
+        self.sum_voltage = 0.0;
+        self.sum_power = 0.0;
+        self.min_power = f32::MAX;
+        self.max_power = f32::MIN;
+        self.min_current = f32::MAX;
+        self.max_current = f32::MIN;
+        self.min_voltage = f32::MAX;
+        self.max_voltage = f32::MIN;
+        self.count = 0;
+    }
+
+    fn fold(&mut self, rec: &Record, power: f32) {
+        self.sum_current += rec.current;
+        self.sum_voltage += rec.voltage;
+        self.sum_power += power;
+        self.min_power = self.min_power.min(power);
+        self.max_power = self.max_power.max(power);
+        self.min_current = self.min_current.min(rec.current);
+        self.max_current = self.max_current.max(rec.current);
+        self.min_voltage = self.min_voltage.min(rec.voltage);
+        self.max_voltage = self.max_voltage.max(rec.voltage);
+        self.last_current = rec.current;
+        self.last_voltage = rec.voltage;
+        self.last_power = power;
+        self.count += 1;
+    }
+
+    fn flush(&mut self) {
+        if self.count == 0 {
+            return;
+        }
+        let cdp = match self.cf {
+            ConsolFn::Average => Cdp {
+                timestamp_ms: self.bucket_start,
+                current: self.sum_current / self.count as f32,
+                voltage: self.sum_voltage / self.count as f32,
+                power: self.sum_power / self.count as f32,
+            },
+            ConsolFn::Min => Cdp {
+                timestamp_ms: self.bucket_start,
+                current: self.min_current,
+                voltage: self.min_voltage,
+                power: self.min_power,
+            },
+            ConsolFn::Max => Cdp {
+                timestamp_ms: self.bucket_start,
+                current: self.max_current,
+                voltage: self.max_voltage,
+                power: self.max_power,
+            },
+            ConsolFn::Last => Cdp {
+                timestamp_ms: self.bucket_start,
+                current: self.last_current,
+                voltage: self.last_voltage,
+                power: self.last_power,
+            },
+        };
+        self.push_cdp(cdp);
+    }
+
+    fn push_cdp(&mut self, cdp: Cdp) {
+        if self.ring.len() < self.rows {
+            self.ring.push(cdp);
+        } else {
+            self.ring[self.head] = cdp;
+            self.head = (self.head + 1) % self.rows;
+        }
+    }
+
+    /// Iterate the archive's CDPs in chronological order.
+    pub fn iter(&self) -> impl Iterator<Item = &Cdp> {
+        let (tail, head) = self.ring.split_at(self.head);
+        head.iter().chain(tail.iter())
+    }
+}
+
+/// The `step_ms`-aligned bucket a timestamp falls into, anchored on
+/// `tz_offset_ms` rather than the raw UTC epoch so that, e.g., a daily
+/// archive's buckets line up with calendar days in the configured
+/// timezone instead of rolling 24h windows from the first sample seen.
+fn bucket_start(timestamp_ms: i64, step_ms: i64, tz_offset_ms: i64) -> i64 {
+    let shifted = timestamp_ms + tz_offset_ms;
+    shifted.div_euclid(step_ms) * step_ms - tz_offset_ms
+}
+
+/// Walk the time-ordered `recs` a single time, folding them into every
+/// archive described by `specs` concurrently. `tz_offset_ms` anchors each
+/// archive's bucket boundaries to the configured timezone.
+pub fn build_archives(recs: &[Record], specs: &[ArchiveSpec], tz_offset_ms: i64) -> Vec<Archive> {
+    let mut archives: Vec<Archive> = specs.iter().map(Archive::new).collect();
+
+    for rec in recs {
+        let power = rec.current * rec.voltage;
+        for archive in &mut archives {
+            let start = bucket_start(rec.timestamp_ms, archive.step_ms, tz_offset_ms);
+            if archive.count == 0 {
+                archive.reset_bucket(start);
+            } else if start != archive.bucket_start {
+                archive.flush();
+                archive.reset_bucket(start);
+            }
+            archive.fold(rec, power);
+        }
+    }
+
+    for archive in &mut archives {
+        archive.flush();
+    }
+
+    archives
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rec(timestamp_ms: i64, current: f32, voltage: f32) -> Record {
+        Record { timestamp_ms, current, voltage }
+    }
+
+    #[test]
+    fn bucket_start_aligns_to_tz_offset() {
+        // A UTC+2 daily bucket should start at 22:00 UTC the previous day,
+        // not at the raw UTC midnight.
+        let day_ms = 24 * 60 * 60 * 1000;
+        let tz_offset_ms = 2 * 60 * 60 * 1000;
+        let one_am_local = 60 * 60 * 1000;
+        assert_eq!(bucket_start(one_am_local, day_ms, tz_offset_ms), -tz_offset_ms);
+    }
+
+    #[test]
+    fn last_archive_tracks_most_recent_sample_per_bucket() {
+        let specs = [ArchiveSpec { cf: ConsolFn::Last, step_ms: 1000, rows: 10 }];
+        let recs = [rec(0, 1.0, 10.0), rec(500, 2.0, 11.0), rec(900, 3.0, 12.0)];
+
+        let archives = build_archives(&recs, &specs, 0);
+        let cdps: Vec<&Cdp> = archives[0].iter().collect();
+
+        assert_eq!(cdps.len(), 1);
+        assert_eq!(cdps[0].current, 3.0);
+        assert_eq!(cdps[0].voltage, 12.0);
+    }
+
+    #[test]
+    fn ring_buffer_evicts_oldest_row_once_full() {
+        let specs = [ArchiveSpec { cf: ConsolFn::Average, step_ms: 1000, rows: 2 }];
+        let recs = [rec(0, 1.0, 10.0), rec(1000, 2.0, 10.0), rec(2000, 3.0, 10.0)];
+
+        let archives = build_archives(&recs, &specs, 0);
+        let timestamps: Vec<i64> = archives[0].iter().map(|cdp| cdp.timestamp_ms).collect();
+
+        // Three buckets were produced but only the newest two rows fit, so
+        // the first bucket (timestamp 0) must have been overwritten.
+        assert_eq!(timestamps, vec![1000, 2000]);
+    }
+}
@@ -0,0 +1,45 @@
+//! Prints per-day min/max/mean power, sample count and covered time span
+//! for the consolidated series.
+
+use chrono::prelude::*;
+
+use crate::rrd::Cdp;
+
+pub fn print_per_day(cdps: &[Cdp], tz: FixedOffset) {
+    println!(
+        "{:<12} {:>8} {:>10} {:>10} {:>10} {:>10}",
+        "Day", "Samples", "Min [W]", "Max [W]", "Mean [W]", "Span [h]"
+    );
+
+    let mut index = 0;
+    while index < cdps.len() {
+        let day = tz
+            .from_utc_datetime(&NaiveDateTime::from_timestamp(cdps[index].timestamp_ms / 1000, 0))
+            .day();
+
+        let mut end = index;
+        while end < cdps.len() {
+            let actual_day = tz
+                .from_utc_datetime(&NaiveDateTime::from_timestamp(cdps[end].timestamp_ms / 1000, 0))
+                .day();
+            if actual_day != day {
+                break;
+            }
+            end += 1;
+        }
+
+        let slice = &cdps[index..end];
+        let date = tz.from_utc_datetime(&NaiveDateTime::from_timestamp(slice[0].timestamp_ms / 1000, 0));
+        let min_power = slice.iter().map(|c| c.power).fold(std::f32::MAX, f32::min);
+        let max_power = slice.iter().map(|c| c.power).fold(std::f32::MIN, f32::max);
+        let mean_power = slice.iter().map(|c| c.power as f64).sum::<f64>() / slice.len() as f64;
+        let span_h = (slice.last().unwrap().timestamp_ms - slice[0].timestamp_ms) as f64 / 3_600_000.0;
+
+        println!(
+            "{:<12} {:>8} {:>10.2} {:>10.2} {:>10.2} {:>10.1}",
+            date.format("%Y-%m-%d"), slice.len(), min_power, max_power, mean_power, span_h
+        );
+
+        index = end;
+    }
+}